@@ -1,6 +1,7 @@
 use extism_pdk::*;
 use node_common::{commands, NodeDistLTS, NodeDistVersion, PackageJson};
 use proto_pdk::*;
+use serde::Deserialize;
 use std::collections::HashMap;
 
 #[host_fn]
@@ -12,6 +13,111 @@ extern "ExtismHost" {
 static NAME: &str = "Node.js";
 static BIN: &str = "node";
 
+// The canonical host, used as the default and as a fallback when a
+// configured mirror is unreachable.
+static DEFAULT_DIST_HOST: &str = "https://nodejs.org/download";
+
+#[derive(Debug, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct NodeToolConfig {
+    pub dist_mirror: Option<String>,
+    pub gpg_verify: bool,
+}
+
+impl Default for NodeToolConfig {
+    fn default() -> Self {
+        Self {
+            dist_mirror: None,
+            // Opt-out, since the signature and release keys may not be
+            // reachable from air-gapped networks or misconfigured mirrors.
+            gpg_verify: true,
+        }
+    }
+}
+
+// The Node.js Release Team's signing key fingerprints, bundled with the
+// plugin so verification doesn't depend on a (possibly compromised) mirror.
+// Keep in sync with https://github.com/nodejs/node#release-keys.
+static RELEASE_KEY_FINGERPRINTS: &[&str] = &[
+    "4ED778F539E3634C779C87C6D7062848A1AB005", // Antoine du Hamel
+    "141F07595B7B3FFE74309A937405533BE57C7D5", // Juan José Arboleda
+    "74F12602B6F1C4E913FAA37AD3A89613643B6201", // Ruy Adorno
+];
+
+// Resolve the distribution host to use, preferring an explicit tool
+// config setting, then an env var (akin to nvm's `NVM_NODEJS_ORG_MIRROR`),
+// and falling back to the canonical `nodejs.org` host.
+fn get_dist_host() -> FnResult<String> {
+    let config = get_tool_config::<NodeToolConfig>()?;
+
+    if let Some(mirror) = config.dist_mirror {
+        if !mirror.is_empty() {
+            return Ok(mirror.trim_end_matches('/').to_owned());
+        }
+    }
+
+    let env = get_proto_environment()?;
+
+    if let Some(mirror) = env.env_vars.get("PROTO_NODE_DIST_MIRROR") {
+        if !mirror.is_empty() {
+            return Ok(mirror.trim_end_matches('/').to_owned());
+        }
+    }
+
+    Ok(DEFAULT_DIST_HOST.to_owned())
+}
+
+// Fetch a channel's `index.json` from the given host, falling back to the
+// canonical host if the mirror is misconfigured or unreachable. Returns the
+// host that actually served the response, since callers build further URLs
+// (download, checksum, signature) against that host, not the configured one.
+fn fetch_dist_index(host: &str, channel: &str) -> FnResult<(String, Vec<NodeDistVersion>)> {
+    let url = format!("{host}/{channel}/index.json");
+
+    match fetch_url(&url) {
+        Ok(response) => Ok((host.to_owned(), response)),
+        Err(error) if host != DEFAULT_DIST_HOST => {
+            fetch_url(format!("{DEFAULT_DIST_HOST}/{channel}/index.json"))
+                .map(|response| (DEFAULT_DIST_HOST.to_owned(), response))
+                .map_err(|_| error)
+        }
+        Err(error) => Err(error),
+    }
+}
+
+// Fetch a plain-text file, such as a detached GPG signature, instead of the
+// JSON that `fetch_url` expects. Falls back to the canonical host the same
+// way `fetch_dist_index` does, returning the host that actually served it.
+fn fetch_dist_text(host: &str, url_path: &str) -> FnResult<(String, String)> {
+    let url = format!("{host}{url_path}");
+
+    match fetch_text(&url) {
+        Ok(text) => Ok((host.to_owned(), text)),
+        Err(error) if host != DEFAULT_DIST_HOST => {
+            fetch_text(&format!("{DEFAULT_DIST_HOST}{url_path}"))
+                .map(|text| (DEFAULT_DIST_HOST.to_owned(), text))
+                .map_err(|_| error)
+        }
+        Err(error) => Err(error),
+    }
+}
+
+fn fetch_text(url: &str) -> FnResult<String> {
+    let response: HttpResponse = http::request(&HttpRequest::new(url), None::<()>)?;
+    let status = response.status_code();
+
+    // A 404/500/etc. error page is still a valid HTTP response, so it
+    // would otherwise come back as `Ok` with the error page as the body.
+    if !(200..300).contains(&status) {
+        return Err(PluginError::Message(format!(
+            "Failed to fetch {url} (status {status})"
+        ))
+        .into());
+    }
+
+    Ok(String::from_utf8_lossy(response.body()).into_owned())
+}
+
 #[plugin_fn]
 pub fn register_tool(Json(_): Json<ToolMetadataInput>) -> FnResult<Json<ToolMetadataOutput>> {
     Ok(Json(ToolMetadataOutput {
@@ -22,6 +128,15 @@ pub fn register_tool(Json(_): Json<ToolMetadataInput>) -> FnResult<Json<ToolMeta
     }))
 }
 
+// Node publishes release candidates under their own index/host, using
+// version strings like `20.0.0-rc.1`. Detect these by their prerelease tag.
+fn is_rc_version(version: &VersionSpec) -> bool {
+    match version {
+        VersionSpec::Version(v) => v.pre.as_str().starts_with("rc"),
+        _ => false,
+    }
+}
+
 fn map_arch(os: HostOS, arch: HostArch) -> Result<String, PluginError> {
     let arch = match arch {
         HostArch::Arm => "armv7l".into(),
@@ -60,15 +175,22 @@ pub fn download_prebuilt(
 
     let arch = map_arch(env.os, env.arch)?;
     let mut version = input.context.version;
-    let mut host = "https://nodejs.org/download/release".to_owned();
+    let dist_host = get_dist_host()?;
+    let mut host = format!("{dist_host}/release");
 
     // When canary, extract the latest version from the index
     if version.is_canary() {
-        let response: Vec<NodeDistVersion> =
-            fetch_url("https://nodejs.org/download/nightly/index.json")?;
+        let (resolved_host, response) = fetch_dist_index(&dist_host, "nightly")?;
 
-        host = "https://nodejs.org/download/nightly".into();
+        // Build the download host from whichever host actually served the
+        // index (the mirror, or the fallback it was served from) rather
+        // than the configured mirror, which may be unreachable.
+        host = format!("{resolved_host}/nightly");
         version = VersionSpec::parse(&response[0].version)?;
+    } else if is_rc_version(&version) {
+        // Release candidates are published to their own index/host, but
+        // otherwise follow the same archive layout as stable releases.
+        host = format!("{dist_host}/rc");
     }
 
     let prefix = match env.os {
@@ -125,8 +247,8 @@ pub fn locate_bins(Json(_): Json<LocateBinsInput>) -> FnResult<Json<LocateBinsOu
 #[plugin_fn]
 pub fn load_versions(Json(_): Json<LoadVersionsInput>) -> FnResult<Json<LoadVersionsOutput>> {
     let mut output = LoadVersionsOutput::default();
-    let response: Vec<NodeDistVersion> =
-        fetch_url("https://nodejs.org/download/release/index.json")?;
+    let dist_host = get_dist_host()?;
+    let (_, response) = fetch_dist_index(&dist_host, "release")?;
 
     for (index, item) in response.iter().enumerate() {
         let version = Version::parse(&item.version[1..])?;
@@ -157,6 +279,25 @@ pub fn load_versions(Json(_): Json<LoadVersionsInput>) -> FnResult<Json<LoadVers
         .aliases
         .insert("latest".into(), output.latest.clone().unwrap());
 
+    // Also surface the newest release candidate (per major) as an alias,
+    // so users can pin to `rc` or `<major>-rc` without knowing the exact
+    // `-rc.N` version string.
+    if let Ok((_, rc_response)) = fetch_dist_index(&dist_host, "rc") {
+        for item in &rc_response {
+            let version = Version::parse(&item.version[1..])?;
+            let major_alias = format!("{}-rc", version.major);
+
+            output
+                .aliases
+                .entry(major_alias)
+                .or_insert_with(|| version.clone());
+
+            if !output.aliases.contains_key("rc") {
+                output.aliases.insert("rc".into(), version.clone());
+            }
+        }
+    }
+
     Ok(Json(output))
 }
 
@@ -173,6 +314,8 @@ pub fn resolve_version(
             "stable"
         } else if alias.starts_with("lts-") || alias.starts_with("lts/") {
             &alias[4..]
+        } else if alias == "rc" || alias.ends_with("-rc") {
+            &alias
         } else {
             return Ok(Json(output));
         };
@@ -222,7 +365,11 @@ pub fn parse_version_file(
 
     if input.file == "package.json" {
         if let Ok(package_json) = json::from_str::<PackageJson>(&input.content) {
-            if let Some(engines) = package_json.engines {
+            // Volta pins are exact versions, whereas `engines` is a loose
+            // range, so prefer the former when both are present.
+            if let Some(constraint) = package_json.volta.and_then(|volta| volta.node) {
+                version = Some(UnresolvedVersionSpec::parse(constraint)?);
+            } else if let Some(engines) = package_json.engines {
                 if let Some(constraint) = engines.get(BIN) {
                     version = Some(UnresolvedVersionSpec::parse(constraint)?);
                 }
@@ -235,6 +382,95 @@ pub fn parse_version_file(
     Ok(Json(ParseVersionFileOutput { version }))
 }
 
+// `verified: true` tells proto the checksum has already been authenticated
+// by this hook (or that it's intentionally not being checked, so proto's
+// own default comparison should run unobstructed). A signature that's
+// fetched but fails to verify must NOT map to `verified: false` here, since
+// that collapses "not checked" and "checked and failed" onto the same
+// value — instead it's a hard error so the install aborts outright.
+#[plugin_fn]
+pub fn verify_checksum(
+    Json(input): Json<VerifyChecksumInput>,
+) -> FnResult<Json<VerifyChecksumOutput>> {
+    let config = get_tool_config::<NodeToolConfig>()?;
+
+    if !config.gpg_verify {
+        return Ok(Json(VerifyChecksumOutput { verified: true }));
+    }
+
+    let version = input.context.version;
+    let dist_host = get_dist_host()?;
+    let channel = if version.is_canary() {
+        "nightly"
+    } else if is_rc_version(&version) {
+        "rc"
+    } else {
+        "release"
+    };
+
+    let sig_url_path = format!("/{channel}/v{version}/SHASUMS256.txt.sig");
+    let signature = match fetch_dist_text(&dist_host, &sig_url_path) {
+        Ok((_, signature)) => signature,
+        // The release channel always publishes a `.sig`; a fetch failure
+        // there is a mirror/network problem worth aborting the install
+        // over. Nightly/rc builds aren't guaranteed to publish one, so
+        // fall through to proto's default SHA-256 check instead.
+        Err(error) if channel == "release" => return Err(error),
+        Err(_) => {
+            host_log!(
+                "No SHASUMS256.txt.sig published for the {} channel, skipping GPG verification",
+                channel
+            );
+            return Ok(Json(VerifyChecksumOutput { verified: true }));
+        }
+    };
+
+    let checksum_file = input.checksum_file.real_path();
+    let work_dir = checksum_file
+        .parent()
+        .unwrap_or_else(|| std::path::Path::new("."));
+    let signature_file = work_dir.join("SHASUMS256.txt.sig");
+    let keyring_file = work_dir.join("nodejs-release-keys.gpg");
+
+    fs::write_file(&signature_file, signature)?;
+
+    // Populate a scratch keyring from the bundled, trusted fingerprints
+    // rather than trusting whatever keys a mirror might serve up.
+    let mut import_args = vec![
+        "--no-default-keyring".to_owned(),
+        "--keyring".to_owned(),
+        keyring_file.to_string_lossy().into_owned(),
+        "--keyserver".to_owned(),
+        "hkps://keys.openpgp.org".to_owned(),
+        "--recv-keys".to_owned(),
+    ];
+    import_args.extend(RELEASE_KEY_FINGERPRINTS.iter().map(|fp| fp.to_string()));
+
+    exec_command!(inherit, "gpg", import_args);
+
+    let result = exec_command!(
+        inherit,
+        "gpg",
+        [
+            "--no-default-keyring",
+            "--keyring",
+            &keyring_file.to_string_lossy(),
+            "--verify",
+            &signature_file.to_string_lossy(),
+            &checksum_file.to_string_lossy(),
+        ]
+    );
+
+    if result.exit_code != 0 {
+        return Err(PluginError::Message(format!(
+            "GPG signature verification of SHASUMS256.txt failed for Node.js v{version}"
+        ))
+        .into());
+    }
+
+    Ok(Json(VerifyChecksumOutput { verified: true }))
+}
+
 #[plugin_fn]
 pub fn install_global(
     Json(input): Json<InstallGlobalInput>,
@@ -261,34 +497,105 @@ pub fn uninstall_global(
 
 #[plugin_fn]
 pub fn post_install(Json(input): Json<InstallHook>) -> FnResult<()> {
-    if input
+    let pinned_pm = if input
         .passthrough_args
         .iter()
-        .any(|arg| arg == "--no-bundled-npm")
+        .any(|arg| arg == "--no-package-manager")
     {
-        return Ok(());
-    }
+        None
+    } else {
+        resolve_pinned_package_manager(&input)?
+    };
+
+    // If `packageManager` itself pins npm, installing the Node-bundled npm
+    // first and then immediately re-pinning it below would just race two
+    // conflicting `proto install npm` calls against each other.
+    let skip_bundled_npm = input
+        .passthrough_args
+        .iter()
+        .any(|arg| arg == "--no-bundled-npm")
+        || matches!(&pinned_pm, Some((tool, _)) if *tool == "npm");
+
+    if !skip_bundled_npm {
+        host_log!("Installing npm that comes bundled with Node.js");
 
-    host_log!("Installing npm that comes bundled with Node.js");
+        let mut args = vec!["install", "npm", "bundled"];
+
+        if input.pinned {
+            args.push("--pin");
+        }
 
-    let mut args = vec!["install", "npm", "bundled"];
+        if !input.passthrough_args.is_empty() {
+            args.push("--");
+            args.extend(
+                input
+                    .passthrough_args
+                    .iter()
+                    .map(|a| a.as_str())
+                    .collect::<Vec<_>>(),
+            );
+        }
 
-    if input.pinned {
-        args.push("--pin");
+        exec_command!(inherit, "proto", args);
     }
 
-    if !input.passthrough_args.is_empty() {
-        args.push("--");
-        args.extend(
-            input
-                .passthrough_args
-                .iter()
-                .map(|a| a.as_str())
-                .collect::<Vec<_>>(),
+    if let Some((tool, version)) = pinned_pm {
+        host_log!(
+            "Installing {} that is pinned via the `packageManager` field",
+            tool
         );
-    }
 
-    exec_command!(inherit, "proto", args);
+        exec_command!(inherit, "proto", ["install", tool, &version, "--pin"]);
+    }
 
     Ok(())
 }
+
+// Maps a Corepack `packageManager` tool name to the proto tool that
+// provides it.
+fn map_package_manager_tool(name: &str) -> Option<&'static str> {
+    match name {
+        "npm" => Some("npm"),
+        "pnpm" => Some("pnpm"),
+        "yarn" => Some("yarn"),
+        _ => None,
+    }
+}
+
+// Reads the `packageManager` field (the Corepack spec, e.g. `pnpm@8.6.0`)
+// out of the project's `package.json` and resolves it to the proto tool
+// and version that should be pinned alongside Node.js.
+fn resolve_pinned_package_manager(input: &InstallHook) -> FnResult<Option<(&'static str, String)>> {
+    let package_json_file = input.context.working_dir.join("package.json");
+
+    if !package_json_file.exists() {
+        return Ok(None);
+    }
+
+    let content = fs::read_file(&package_json_file)?;
+
+    let Ok(package_json) = json::from_str::<PackageJson>(&content) else {
+        return Ok(None);
+    };
+
+    let Some(package_manager) = package_json.package_manager else {
+        return Ok(None);
+    };
+
+    // The spec is `name@version[+hash]`; the integrity hash isn't needed
+    // to resolve and install the tool.
+    let spec = package_manager
+        .split('+')
+        .next()
+        .unwrap_or(&package_manager);
+
+    let Some((name, version)) = spec.split_once('@') else {
+        return Ok(None);
+    };
+
+    let Some(tool) = map_package_manager_tool(name) else {
+        return Ok(None);
+    };
+
+    Ok(Some((tool, version.to_owned())))
+}